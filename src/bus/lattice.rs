@@ -12,12 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::router::{BalancePolicy, Router};
 use crate::{Invocation, InvocationResponse, Result};
 use crossbeam::{Receiver, Sender};
 use nats;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use wascc_codec::{deserialize, serialize};
 
 const LATTICE_HOST_KEY: &str = "LATTICE_HOST"; // env var name
@@ -25,43 +28,320 @@ const DEFAULT_LATTICE_HOST: &str = "127.0.0.1"; // default mode is anonymous via
 const LATTICE_RPC_TIMEOUT_KEY: &str = "LATTICE_RPC_TIMEOUT_MILLIS";
 const DEFAULT_LATTICE_RPC_TIMEOUT_MILLIS: u64 = 500;
 const LATTICE_CREDSFILE_KEY: &str = "LATTICE_CREDS_FILE";
+const LATTICE_TLS_CA_KEY: &str = "LATTICE_TLS_CA";
+const LATTICE_TLS_CLIENT_CERT_KEY: &str = "LATTICE_TLS_CLIENT_CERT";
+const LATTICE_TLS_CLIENT_KEY_KEY: &str = "LATTICE_TLS_CLIENT_KEY";
+const LATTICE_AUTH_TOKEN_KEY: &str = "LATTICE_AUTH_TOKEN";
+
+const LATTICE_ANTIFORGERY_SYNC_MILLIS_KEY: &str = "LATTICE_ANTIFORGERY_SYNC_MILLIS";
+const DEFAULT_LATTICE_ANTIFORGERY_SYNC_MILLIS: u64 = 60_000;
+const ANTIFORGERY_EVENTS_SUBJECT: &str = "wasmbus.events.antiforgery";
+
+const LATTICE_RECONNECT_BASE_MILLIS_KEY: &str = "LATTICE_RECONNECT_BASE_MILLIS";
+const DEFAULT_LATTICE_RECONNECT_BASE_MILLIS: u64 = 100;
+const LATTICE_RECONNECT_MAX_MILLIS_KEY: &str = "LATTICE_RECONNECT_MAX_MILLIS";
+const DEFAULT_LATTICE_RECONNECT_MAX_MILLIS: u64 = 30_000;
+const LATTICE_RECONNECT_MAX_RETRIES_KEY: &str = "LATTICE_RECONNECT_MAX_RETRIES";
+const DEFAULT_LATTICE_RECONNECT_MAX_RETRIES: u64 = 0; // 0 == infinite
+
+const LATTICE_DISCOVERY_TTL_MILLIS_KEY: &str = "LATTICE_DISCOVERY_TTL_MILLIS";
+const DEFAULT_LATTICE_DISCOVERY_TTL_MILLIS: u64 = 30_000;
+const DISCOVERY_SUBJECT_PREFIX: &str = "wasmbus.discovery.";
+
+const LATTICE_MAX_CONCURRENT_KEY: &str = "LATTICE_MAX_CONCURRENT";
+const DEFAULT_LATTICE_MAX_CONCURRENT: usize = 4;
+const LATTICE_MAX_CONCURRENT_OUTBOUND_KEY: &str = "LATTICE_MAX_CONCURRENT_OUTBOUND";
+const DEFAULT_LATTICE_MAX_CONCURRENT_OUTBOUND: usize = 4;
+const OVERLOADED_ERROR: &str = "host overloaded";
+
+/// A single block-listed host origin, gossiped to the rest of the lattice. The
+/// `expires_at` field, when present, is a Unix timestamp (seconds) after which
+/// hosts stop treating the origin as blocked; the entry itself stays in the set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BlockListEntry {
+    origin: String,
+    expires_at: Option<u64>,
+}
+
+/// Grow-only set (G-Set) of block-listed host origins. Entries are only ever added
+/// or have their expiry extended, so the set converges on every host regardless of
+/// the order in which gossip messages are delivered.
+type BlockList = Arc<RwLock<HashMap<String, Option<u64>>>>;
+
+// Everything needed to re-establish a subscription after the underlying NATS
+// connection is lost and reconnected, since subscriptions don't survive that.
+struct SubscriptionState {
+    sender: Sender<Invocation>,
+    receiver: Receiver<InvocationResponse>,
+    handler: nats::subscription::Handler,
+    route_index: usize,
+}
+
+type SubsMap = Arc<RwLock<HashMap<String, SubscriptionState>>>;
+
+// A subscription that isn't tied to a single invocation subject (the antiforgery
+// events feed, the discovery events feed) but still needs to be re-established
+// after a reconnect, the same as everything in `SubsMap`.
+struct ManagedSubscription {
+    resubscribe: Box<dyn Fn(&nats::Connection) -> Result<nats::subscription::Handler> + Send + Sync>,
+    handler: nats::subscription::Handler,
+}
+
+type MiscSubs = Arc<RwLock<HashMap<String, ManagedSubscription>>>;
+
+/// An announcement that an actor or capability provider is reachable on some
+/// host in the lattice, rendezvous-style. Entries are TTL-refreshed by the
+/// advertising host and dropped by every other host once `expires_at` lapses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registration {
+    pub host_id: String,
+    pub entity_id: String,
+    pub metadata: HashMap<String, String>,
+    pub expires_at: u64,
+}
+
+// namespace -> entity_id -> most recently seen registration
+type DiscoveryMap = Arc<RwLock<HashMap<String, HashMap<String, Registration>>>>;
+
+/// Returned by `DistributedBus::register`. Holds the background refresh
+/// thread's registration open; dropping it (or calling `deregister`
+/// explicitly) stops the TTL refresh, letting the entry lapse on every other
+/// host once its current `expires_at` passes.
+pub struct RegistrationGuard {
+    stop: Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl RegistrationGuard {
+    /// Stops the background refresh immediately. Equivalent to dropping the
+    /// guard, spelled out for call sites that want to be explicit about it.
+    pub fn deregister(self) {}
+}
+
+impl Drop for RegistrationGuard {
+    fn drop(&mut self) {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// Released back into the gate when dropped, so a permit is always returned even
+// if the invocation path panics or returns early.
+struct Permit {
+    release: Sender<()>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let _ = self.release.send(());
+    }
+}
+
+/// Bounds the number of in-flight invocations so a burst of traffic can't
+/// exhaust threads/memory on this host. Backed by a bounded crossbeam channel
+/// pre-filled with `max` tokens: acquiring a permit is a `recv`, releasing one
+/// is a `send`. Inbound and outbound invocations are gated by separate
+/// instances (see `DistributedBus::inbound_concurrency`/`outbound_concurrency`)
+/// so a handler that calls back out through `invoke` can't starve itself
+/// waiting on a permit its own inbound dispatch is holding.
+#[derive(Clone)]
+struct ConcurrencyGate {
+    acquire: Receiver<()>,
+    release: Sender<()>,
+    max: usize,
+}
+
+impl ConcurrencyGate {
+    fn new(max: usize) -> Self {
+        let (release, acquire) = crossbeam::bounded(max);
+        for _ in 0..max {
+            release.send(()).unwrap();
+        }
+        ConcurrencyGate {
+            acquire,
+            release,
+            max,
+        }
+    }
+
+    fn acquire_blocking(&self) -> Permit {
+        self.acquire.recv().unwrap();
+        Permit {
+            release: self.release.clone(),
+        }
+    }
+
+    fn try_acquire(&self) -> Option<Permit> {
+        self.acquire.try_recv().ok().map(|_| Permit {
+            release: self.release.clone(),
+        })
+    }
+
+    /// Current number of free permits, for observability.
+    fn available_permits(&self) -> usize {
+        self.acquire.len()
+    }
+}
 
 pub(crate) struct DistributedBus {
     nc: nats::Connection,
-    subs: Arc<RwLock<HashMap<String, nats::subscription::Handler>>>,
+    subs: SubsMap,
+    misc_subs: MiscSubs,
     req_timeout: Duration,
+    blocklist: BlockList,
+    discovery: DiscoveryMap,
+    inbound_concurrency: ConcurrencyGate,
+    outbound_concurrency: ConcurrencyGate,
+    router: Arc<Router>,
 }
 
 impl DistributedBus {
-    pub fn new() -> Self {
-        let nc = get_connection();
+    pub fn new() -> Result<Self> {
+        let subs: SubsMap = Arc::new(RwLock::new(HashMap::new()));
+        let misc_subs: MiscSubs = Arc::new(RwLock::new(HashMap::new()));
+        let blocklist: BlockList = Arc::new(RwLock::new(HashMap::new()));
+        let discovery: DiscoveryMap = Arc::new(RwLock::new(HashMap::new()));
+        let inbound_concurrency = ConcurrencyGate::new(get_max_concurrent());
+        let outbound_concurrency = ConcurrencyGate::new(get_max_concurrent_outbound());
+        let router = Arc::new(Router::new(BalancePolicy::default()));
+        let nc = get_connection(
+            subs.clone(),
+            misc_subs.clone(),
+            blocklist.clone(),
+            inbound_concurrency.clone(),
+        )?;
+
+        subscribe_antiforgery_events(&nc, blocklist.clone(), &misc_subs);
+        spawn_antiforgery_sync(nc.clone(), blocklist.clone());
+        subscribe_discovery_events(&nc, discovery.clone(), &misc_subs)?;
 
         info!("Initialized Message Bus (lattice)");
-        DistributedBus {
+        Ok(DistributedBus {
             nc,
-            subs: Arc::new(RwLock::new(HashMap::new())),
+            subs,
+            misc_subs,
             req_timeout: get_timeout(),
-        }
+            blocklist,
+            discovery,
+            inbound_concurrency,
+            outbound_concurrency,
+            router,
+        })
+    }
+
+    /// Number of inbound invocation permits currently available (i.e. not in
+    /// flight), for observability.
+    pub fn available_permits(&self) -> usize {
+        self.inbound_concurrency.available_permits()
+    }
+
+    /// Number of outbound invocation permits currently available (i.e. not in
+    /// flight), for observability.
+    pub fn available_outbound_permits(&self) -> usize {
+        self.outbound_concurrency.available_permits()
+    }
+
+    /// Periodically announces `entity_id` (an actor public key, or a capability
+    /// contract+link name) as reachable on this host, under `namespace`. The
+    /// announcement is TTL-refreshed in the background until the returned
+    /// guard is dropped (or `RegistrationGuard::deregister` is called), at
+    /// which point the entry is left to expire on every other host.
+    pub fn register(
+        &self,
+        namespace: &str,
+        host_id: &str,
+        entity_id: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<RegistrationGuard> {
+        let subject = discovery_subject(namespace);
+        let ttl = get_discovery_ttl();
+        let nc = self.nc.clone();
+        let host_id = host_id.to_string();
+        let entity_id = entity_id.to_string();
+        let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        announce(&nc, &subject, &host_id, &entity_id, &metadata, ttl)?;
+
+        let stop_flag = stop.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(ttl / 2);
+            if stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
+                break;
+            }
+            if let Err(e) = announce(&nc, &subject, &host_id, &entity_id, &metadata, ttl) {
+                error!("Failed to refresh discovery registration for '{}': {}", entity_id, e);
+            }
+        });
+        Ok(RegistrationGuard { stop })
+    }
+
+    /// Returns the non-expired registrations this host has observed (including
+    /// its own) under `namespace`: one per entity ID, the most recently received
+    /// announcement for it, so only one of several hosts announcing the same
+    /// entity is returned.
+    pub fn discover(&self, namespace: &str) -> Vec<Registration> {
+        active_registrations(&self.discovery, namespace, now_unix())
     }
 
+    /// Subscribes `(sender, receiver)` to handle invocations sent to `subject`,
+    /// both over NATS (for invocations arriving from the rest of the lattice)
+    /// and as a local route on `self.router` (so this host's own `invoke`
+    /// calls can hand off directly instead of always round-tripping through
+    /// NATS to reach a subject it already serves itself).
     pub fn subscribe(
         &self,
         subject: &str,
         sender: Sender<Invocation>,
         receiver: Receiver<InvocationResponse>,
     ) -> Result<()> {
-        let sub = self
-            .nc
-            .queue_subscribe(subject, subject)?
-            .with_handler(move |msg| {
-                handle_invocation(&msg, sender.clone(), receiver.clone());
-                Ok(())
-            });
-        self.subs.write().unwrap().insert(subject.to_string(), sub);
+        let handler = queue_subscribe_with_handler(
+            &self.nc,
+            subject,
+            self.blocklist.clone(),
+            self.inbound_concurrency.clone(),
+            sender.clone(),
+            receiver.clone(),
+        )?;
+        let route_index = self.add_local_route(subject.to_string(), sender.clone(), receiver.clone());
+        self.subs.write().unwrap().insert(
+            subject.to_string(),
+            SubscriptionState {
+                sender,
+                receiver,
+                handler,
+                route_index,
+            },
+        );
         Ok(())
     }
 
+    /// Registers `(sender, receiver)` as a locally-dispatchable pair for
+    /// `subject`, so `invoke` can hand invocations directly to it (subject to
+    /// the router's load-balance policy and circuit breaker) instead of
+    /// always round-tripping through NATS. Returns the slot index to pass to
+    /// `remove_local_route` when the pair goes away.
+    pub fn add_local_route(
+        &self,
+        subject: String,
+        sender: Sender<Invocation>,
+        receiver: Receiver<InvocationResponse>,
+    ) -> usize {
+        self.router.add_route(subject, sender, receiver)
+    }
+
+    /// Unregisters the local pair previously returned by `add_local_route`.
+    pub fn remove_local_route(&self, subject: &str, index: usize) {
+        self.router.remove_route(subject, index);
+    }
+
     pub fn invoke(&self, subject: &str, inv: Invocation) -> Result<InvocationResponse> {
+        let _permit = self.outbound_concurrency.acquire_blocking();
+
+        if let Some((index, (sender, receiver))) = self.router.get_pair(subject) {
+            sender.send(inv).unwrap();
+            let ir: InvocationResponse = receiver.recv().unwrap();
+            self.router.record_result(subject, index, ir.error.is_none());
+            return Ok(ir);
+        }
+
         let resp = self
             .nc
             .request_timeout(&subject, &serialize(inv)?, self.req_timeout)?;
@@ -70,27 +350,141 @@ impl DistributedBus {
     }
 
     pub fn unsubscribe(&self, subject: &str) -> Result<()> {
-        if let Some(sub) = self.subs.write().unwrap().remove(subject) {
-            sub.unsubscribe()?;
+        if let Some(state) = self.subs.write().unwrap().remove(subject) {
+            self.remove_local_route(subject, state.route_index);
+            state.handler.unsubscribe()?;
         }
         Ok(())
     }
 }
 
+fn queue_subscribe_with_handler(
+    nc: &nats::Connection,
+    subject: &str,
+    blocklist: BlockList,
+    inbound_concurrency: ConcurrencyGate,
+    sender: Sender<Invocation>,
+    receiver: Receiver<InvocationResponse>,
+) -> Result<nats::subscription::Handler> {
+    let nc = nc.clone();
+    let handler = nc
+        .clone()
+        .queue_subscribe(subject, subject)?
+        .with_handler(move |msg| {
+            handle_invocation(
+                &msg,
+                &nc,
+                blocklist.clone(),
+                inbound_concurrency.clone(),
+                sender.clone(),
+                receiver.clone(),
+            );
+            Ok(())
+        });
+    Ok(handler)
+}
+
+// Re-subscribes every subject the host cares about after a reconnect, since NATS
+// subscriptions don't survive a full disconnect/reconnect cycle. This covers both
+// per-invocation-subject subscriptions (`subs`) and the fixed gossip feeds
+// (`misc_subs`: antiforgery events, discovery events) — missing either leaves
+// that feed permanently dark for the rest of the process after the first drop.
+fn resubscribe_all(
+    nc: &nats::Connection,
+    subs: &SubsMap,
+    misc_subs: &MiscSubs,
+    blocklist: &BlockList,
+    inbound_concurrency: &ConcurrencyGate,
+) {
+    let subjects: Vec<String> = subs.read().unwrap().keys().cloned().collect();
+    for subject in subjects {
+        let (sender, receiver) = match subs.read().unwrap().get(&subject) {
+            Some(state) => (state.sender.clone(), state.receiver.clone()),
+            None => continue,
+        };
+        match queue_subscribe_with_handler(
+            nc,
+            &subject,
+            blocklist.clone(),
+            inbound_concurrency.clone(),
+            sender,
+            receiver,
+        ) {
+            Ok(handler) => {
+                if let Some(state) = subs.write().unwrap().get_mut(&subject) {
+                    state.handler = handler;
+                }
+                info!("Re-established lattice subscription on '{}'", subject);
+            }
+            Err(e) => error!(
+                "Failed to re-establish lattice subscription on '{}' after reconnect: {}",
+                subject, e
+            ),
+        }
+    }
+
+    let misc_keys: Vec<String> = misc_subs.read().unwrap().keys().cloned().collect();
+    for key in misc_keys {
+        let result = misc_subs
+            .read()
+            .unwrap()
+            .get(&key)
+            .map(|entry| (entry.resubscribe)(nc));
+        match result {
+            Some(Ok(handler)) => {
+                if let Some(entry) = misc_subs.write().unwrap().get_mut(&key) {
+                    entry.handler = handler;
+                }
+                info!("Re-established lattice subscription on '{}'", key);
+            }
+            Some(Err(e)) => error!(
+                "Failed to re-establish lattice subscription on '{}' after reconnect: {}",
+                key, e
+            ),
+            None => {}
+        }
+    }
+}
+
 // This function is invoked any time an invocation is _received_ by the message bus
 fn handle_invocation(
     msg: &nats::Message,
+    nc: &nats::Connection,
+    blocklist: BlockList,
+    inbound_concurrency: ConcurrencyGate,
     sender: Sender<Invocation>,
     receiver: Receiver<InvocationResponse>,
 ) {
     let inv = invocation_from_msg(msg);
-    //TODO: when we implement the issue, check that the invocation's origin host is not in the block list
+
+    let _permit = match inbound_concurrency.try_acquire() {
+        Some(permit) => permit,
+        None => {
+            warn!(
+                "Rejecting invocation from '{}': host overloaded ({} max in-flight)",
+                inv.origin, inbound_concurrency.max
+            );
+            let inv_r = InvocationResponse::error(&inv, OVERLOADED_ERROR);
+            msg.respond(serialize(inv_r).unwrap()).unwrap();
+            return;
+        }
+    };
+
+    if is_blocked(&blocklist, &inv.origin) {
+        error!("Rejected invocation from block-listed origin '{}'", inv.origin);
+        let inv_r = InvocationResponse::error(
+            &inv,
+            &format!("Origin '{}' is block-listed on this lattice", inv.origin),
+        );
+        msg.respond(serialize(inv_r).unwrap()).unwrap();
+        return;
+    }
+
     if let Err(e) = inv.validate_antiforgery() {
         error!("Invocation Antiforgery check failure: {}", e);
+        block_origin(nc, &blocklist, inv.origin.clone(), None);
         let inv_r = InvocationResponse::error(&inv, &format!("Antiforgery check failure: {}", e));
         msg.respond(serialize(inv_r).unwrap()).unwrap();
-    // TODO: when we implement the issue, publish an antiforgery check event on wasmbus.events
-    // TODO: when we implement the issue, add the host origin of the invocation to the global lattice block list
     } else {
         sender.send(inv).unwrap();
         let inv_r = receiver.recv().unwrap();
@@ -103,10 +497,283 @@ fn invocation_from_msg(msg: &nats::Message) -> Invocation {
     i
 }
 
+fn is_blocked(blocklist: &BlockList, origin: &str) -> bool {
+    match blocklist.read().unwrap().get(origin) {
+        Some(Some(expires_at)) => *expires_at > now_unix(),
+        Some(None) => true,
+        None => false,
+    }
+}
+
+// Adds `origin` to the local block list and gossips it to the rest of the lattice.
+fn block_origin(nc: &nats::Connection, blocklist: &BlockList, origin: String, expires_at: Option<u64>) {
+    merge_entry(blocklist, &BlockListEntry {
+        origin: origin.clone(),
+        expires_at,
+    });
+    let digest = vec![BlockListEntry { origin, expires_at }];
+    if let Err(e) = nc.publish(ANTIFORGERY_EVENTS_SUBJECT, &serialize(&digest).unwrap()) {
+        error!("Failed to publish antiforgery block-list event: {}", e);
+    }
+}
+
+// Merges a single gossiped entry into the local set, keeping the longer-lived
+// expiry (or no expiry at all) whenever an entry already exists.
+fn merge_entry(blocklist: &BlockList, entry: &BlockListEntry) {
+    let mut b = blocklist.write().unwrap();
+    let merged = match (b.get(&entry.origin).cloned(), entry.expires_at) {
+        (Some(None), _) | (_, None) => None,
+        (Some(Some(existing)), Some(incoming)) => Some(existing.max(incoming)),
+        (None, Some(incoming)) => Some(incoming),
+    };
+    b.insert(entry.origin.clone(), merged);
+}
+
+fn antiforgery_subscribe_once(
+    nc: &nats::Connection,
+    blocklist: BlockList,
+) -> Result<nats::subscription::Handler> {
+    let handler = nc
+        .subscribe(ANTIFORGERY_EVENTS_SUBJECT)?
+        .with_handler(move |msg| {
+            match deserialize::<Vec<BlockListEntry>>(&msg.data) {
+                Ok(entries) => {
+                    for entry in &entries {
+                        merge_entry(&blocklist, entry);
+                    }
+                }
+                Err(e) => error!("Failed to deserialize antiforgery block-list event: {}", e),
+            }
+            Ok(())
+        });
+    Ok(handler)
+}
+
+// Subscribes to the reserved antiforgery events subject and merges every
+// gossiped block-list digest (one or more entries) into the local set. The
+// subscription is tracked in `misc_subs` so `resubscribe_all` can re-establish
+// it (and keep its `Handler` alive) after a reconnect.
+fn subscribe_antiforgery_events(nc: &nats::Connection, blocklist: BlockList, misc_subs: &MiscSubs) {
+    match antiforgery_subscribe_once(nc, blocklist.clone()) {
+        Ok(handler) => {
+            misc_subs.write().unwrap().insert(
+                ANTIFORGERY_EVENTS_SUBJECT.to_string(),
+                ManagedSubscription {
+                    resubscribe: Box::new(move |nc| {
+                        antiforgery_subscribe_once(nc, blocklist.clone())
+                    }),
+                    handler,
+                },
+            );
+        }
+        Err(e) => error!("Failed to subscribe to antiforgery events: {}", e),
+    }
+}
+
+// Periodically re-publishes the entire local block list so that hosts joining
+// the lattice late (or that missed a gossip message) converge without needing
+// any out-of-band coordination.
+fn spawn_antiforgery_sync(nc: nats::Connection, blocklist: BlockList) {
+    let interval = Duration::from_millis(get_millis_env(
+        LATTICE_ANTIFORGERY_SYNC_MILLIS_KEY,
+        DEFAULT_LATTICE_ANTIFORGERY_SYNC_MILLIS,
+    ));
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        let digest: Vec<BlockListEntry> = blocklist
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(origin, expires_at)| BlockListEntry {
+                origin: origin.clone(),
+                expires_at: *expires_at,
+            })
+            .collect();
+        if digest.is_empty() {
+            continue;
+        }
+        if let Err(e) = nc.publish(ANTIFORGERY_EVENTS_SUBJECT, &serialize(&digest).unwrap()) {
+            error!("Failed to publish antiforgery anti-entropy digest: {}", e);
+        }
+    });
+}
+
+fn discovery_subject(namespace: &str) -> String {
+    format!("{}{}", DISCOVERY_SUBJECT_PREFIX, namespace)
+}
+
+// Registrations are never removed as they expire, only filtered out at read
+// time, so a lapsed entry is naturally replaced if the advertising host comes
+// back and re-announces before anyone reaps it.
+fn active_registrations(discovery: &DiscoveryMap, namespace: &str, now: u64) -> Vec<Registration> {
+    discovery
+        .read()
+        .unwrap()
+        .get(namespace)
+        .map(|entities| {
+            entities
+                .values()
+                .filter(|r| r.expires_at > now)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn announce(
+    nc: &nats::Connection,
+    subject: &str,
+    host_id: &str,
+    entity_id: &str,
+    metadata: &HashMap<String, String>,
+    ttl: Duration,
+) -> Result<()> {
+    let reg = Registration {
+        host_id: host_id.to_string(),
+        entity_id: entity_id.to_string(),
+        metadata: metadata.clone(),
+        expires_at: now_unix() + ttl.as_secs(),
+    };
+    nc.publish(subject, &serialize(&reg)?)?;
+    Ok(())
+}
+
+fn discovery_subscribe_once(
+    nc: &nats::Connection,
+    discovery: DiscoveryMap,
+) -> Result<nats::subscription::Handler> {
+    let wildcard = format!("{}*", DISCOVERY_SUBJECT_PREFIX);
+    let handler = nc.subscribe(&wildcard)?.with_handler(move |msg| {
+        let namespace = match msg.subject.strip_prefix(DISCOVERY_SUBJECT_PREFIX) {
+            Some(ns) => ns.to_string(),
+            None => return Ok(()),
+        };
+        match deserialize::<Registration>(&msg.data) {
+            Ok(reg) => {
+                discovery
+                    .write()
+                    .unwrap()
+                    .entry(namespace)
+                    .or_insert_with(HashMap::new)
+                    .insert(reg.entity_id.clone(), reg);
+            }
+            Err(e) => error!("Failed to deserialize discovery registration: {}", e),
+        }
+        Ok(())
+    });
+    Ok(handler)
+}
+
+// Subscribes to every discovery subject (one reserved subject per namespace, all
+// sharing the `wasmbus.discovery.` prefix) and keeps the most recent, TTL-bearing
+// registration seen for each (namespace, entity_id) pair. Tracked in `misc_subs`
+// so a reconnect re-establishes it instead of leaving discovery permanently dark
+// (and so a host's own announcement is still heard echoed back through it).
+fn subscribe_discovery_events(
+    nc: &nats::Connection,
+    discovery: DiscoveryMap,
+    misc_subs: &MiscSubs,
+) -> Result<()> {
+    let handler = discovery_subscribe_once(nc, discovery.clone())?;
+    misc_subs.write().unwrap().insert(
+        format!("{}*", DISCOVERY_SUBJECT_PREFIX),
+        ManagedSubscription {
+            resubscribe: Box::new(move |nc| discovery_subscribe_once(nc, discovery.clone())),
+            handler,
+        },
+    );
+    Ok(())
+}
+
+fn get_max_concurrent() -> usize {
+    get_usize_env(LATTICE_MAX_CONCURRENT_KEY, DEFAULT_LATTICE_MAX_CONCURRENT)
+}
+
+fn get_max_concurrent_outbound() -> usize {
+    get_usize_env(
+        LATTICE_MAX_CONCURRENT_OUTBOUND_KEY,
+        DEFAULT_LATTICE_MAX_CONCURRENT_OUTBOUND,
+    )
+}
+
+fn get_usize_env(var: &str, default: usize) -> usize {
+    match std::env::var(var) {
+        Ok(val) => {
+            if val.is_empty() {
+                default
+            } else {
+                val.parse().unwrap_or(default)
+            }
+        }
+        Err(_) => default,
+    }
+}
+
+fn get_discovery_ttl() -> Duration {
+    Duration::from_millis(get_millis_env(
+        LATTICE_DISCOVERY_TTL_MILLIS_KEY,
+        DEFAULT_LATTICE_DISCOVERY_TTL_MILLIS,
+    ))
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
 fn get_credsfile() -> Option<String> {
     std::env::var(LATTICE_CREDSFILE_KEY).ok()
 }
 
+fn get_auth_token() -> Option<String> {
+    std::env::var(LATTICE_AUTH_TOKEN_KEY).ok()
+}
+
+// Client TLS material for a mutually-authenticated lattice connection. Present
+// only when at least one of the `LATTICE_TLS_*` env vars is set.
+#[derive(Clone)]
+struct TlsConfig {
+    ca: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+}
+
+fn load_tls_config() -> Result<Option<TlsConfig>> {
+    let ca = std::env::var(LATTICE_TLS_CA_KEY).ok();
+    let client_cert = std::env::var(LATTICE_TLS_CLIENT_CERT_KEY).ok();
+    let client_key = std::env::var(LATTICE_TLS_CLIENT_KEY_KEY).ok();
+
+    if ca.is_none() && client_cert.is_none() && client_key.is_none() {
+        return Ok(None);
+    }
+
+    if client_cert.is_some() != client_key.is_some() {
+        return Err(format!(
+            "Lattice mTLS is half-configured: both '{}' and '{}' must be set together, not just one",
+            LATTICE_TLS_CLIENT_CERT_KEY, LATTICE_TLS_CLIENT_KEY_KEY
+        )
+        .into());
+    }
+
+    for path in [&ca, &client_cert, &client_key].iter().filter_map(|p| p.as_ref()) {
+        if !std::path::Path::new(path).is_file() {
+            return Err(format!(
+                "Lattice TLS file '{}' does not exist or could not be read",
+                path
+            )
+            .into());
+        }
+    }
+
+    Ok(Some(TlsConfig {
+        ca,
+        client_cert,
+        client_key,
+    }))
+}
+
 fn get_env(var: &str, default: &str) -> String {
     match std::env::var(var) {
         Ok(val) => {
@@ -120,27 +787,305 @@ fn get_env(var: &str, default: &str) -> String {
     }
 }
 
-fn get_connection() -> nats::Connection {
-    let host = get_env(LATTICE_HOST_KEY, DEFAULT_LATTICE_HOST);
-    info!("Lattice Host: {}", host);
+fn get_millis_env(var: &str, default: u64) -> u64 {
+    match std::env::var(var) {
+        Ok(val) => {
+            if val.is_empty() {
+                default
+            } else {
+                val.parse().unwrap_or(default)
+            }
+        }
+        Err(_) => default,
+    }
+}
+
+// Builds a fresh set of connection options for a single connect attempt. This is
+// re-invoked on every retry since `nats::ConnectionOptions` is consumed by `connect`.
+fn build_options(
+    nc_cell: Arc<RwLock<Option<nats::Connection>>>,
+    subs: SubsMap,
+    misc_subs: MiscSubs,
+    blocklist: BlockList,
+    inbound_concurrency: ConcurrencyGate,
+    tls: Option<TlsConfig>,
+    auth_token: Option<String>,
+) -> nats::ConnectionOptions {
     let mut opts = if let Some(creds) = get_credsfile() {
         nats::ConnectionOptions::with_credentials(creds)
     } else {
         nats::ConnectionOptions::new()
     };
-    opts = opts.with_name("waSCC Lattice");
-    opts.connect(&host).unwrap()
+
+    if let Some(tls) = &tls {
+        opts = opts.tls_required(true);
+        if let Some(ca) = &tls.ca {
+            opts = opts.add_root_certificate(ca);
+        }
+        if let (Some(cert), Some(key)) = (&tls.client_cert, &tls.client_key) {
+            opts = opts.client_cert(cert, key);
+        }
+    }
+
+    if let Some(token) = &auth_token {
+        opts = opts.with_token(token);
+    }
+
+    opts = opts
+        .with_name("waSCC Lattice")
+        .disconnect_callback(|| warn!("Lost connection to lattice NATS host"))
+        .reconnect_callback(move || {
+            info!("Reconnected to lattice NATS host, re-establishing subscriptions");
+            match nc_cell.read().unwrap().clone() {
+                Some(nc) => resubscribe_all(&nc, &subs, &misc_subs, &blocklist, &inbound_concurrency),
+                None => warn!(
+                    "Reconnected before the lattice connection handle was recorded; \
+                     subscriptions were not re-established for this reconnect cycle"
+                ),
+            }
+        });
+    opts
 }
 
-fn get_timeout() -> Duration {
-    match std::env::var(LATTICE_RPC_TIMEOUT_KEY) {
-        Ok(val) => {
-            if val.is_empty() {
-                Duration::from_millis(DEFAULT_LATTICE_RPC_TIMEOUT_MILLIS)
-            } else {
-                Duration::from_millis(val.parse().unwrap_or(DEFAULT_LATTICE_RPC_TIMEOUT_MILLIS))
+// Connects to the lattice NATS host, retrying with exponential backoff and jitter
+// on failure rather than panicking (a momentarily unavailable NATS server should
+// not crash host startup). `max_retries == 0` retries forever.
+fn connect_with_backoff(
+    host: &str,
+    nc_cell: &Arc<RwLock<Option<nats::Connection>>>,
+    build: impl Fn() -> nats::ConnectionOptions,
+) -> Result<nats::Connection> {
+    let base = Duration::from_millis(get_millis_env(
+        LATTICE_RECONNECT_BASE_MILLIS_KEY,
+        DEFAULT_LATTICE_RECONNECT_BASE_MILLIS,
+    ));
+    let max = Duration::from_millis(get_millis_env(
+        LATTICE_RECONNECT_MAX_MILLIS_KEY,
+        DEFAULT_LATTICE_RECONNECT_MAX_MILLIS,
+    ));
+    let max_retries = get_millis_env(
+        LATTICE_RECONNECT_MAX_RETRIES_KEY,
+        DEFAULT_LATTICE_RECONNECT_MAX_RETRIES,
+    );
+
+    let mut attempt: u64 = 0;
+    let mut delay = base;
+    loop {
+        attempt += 1;
+        match build().connect(host) {
+            Ok(nc) => {
+                // Recorded here, as close to `connect` returning as possible, so
+                // the `reconnect_callback` registered on these options (which can
+                // only fire once this connection exists) finds a populated cell.
+                *nc_cell.write().unwrap() = Some(nc.clone());
+                return Ok(nc);
             }
+            Err(e) => {
+                if retries_exhausted(attempt, max_retries) {
+                    return Err(format!(
+                        "Failed to connect to lattice NATS host '{}' after {} attempt(s): {}",
+                        host, attempt, e
+                    )
+                    .into());
+                }
+                let jitter = Duration::from_millis(
+                    rand::thread_rng().gen_range(0, delay.as_millis() as u64 + 1),
+                );
+                warn!(
+                    "Lattice NATS connection attempt {} failed ({}), retrying in ~{:?}",
+                    attempt, e, delay
+                );
+                std::thread::sleep(delay + jitter);
+                delay = std::cmp::min(delay * 2, max);
+            }
+        }
+    }
+}
+
+// `max_retries == 0` means retry forever. Otherwise `attempt` (1-indexed, the
+// attempt that just failed) must reach `max_retries` before giving up, so a
+// `max_retries` of 1 fails fast after a single try with no retries at all.
+fn retries_exhausted(attempt: u64, max_retries: u64) -> bool {
+    max_retries > 0 && attempt >= max_retries
+}
+
+fn get_connection(
+    subs: SubsMap,
+    misc_subs: MiscSubs,
+    blocklist: BlockList,
+    inbound_concurrency: ConcurrencyGate,
+) -> Result<nats::Connection> {
+    let host = get_env(LATTICE_HOST_KEY, DEFAULT_LATTICE_HOST);
+    info!("Lattice Host: {}", host);
+
+    // Resolved once up front: a missing/unreadable TLS file is a configuration
+    // error, not a transient failure, so it shouldn't be retried.
+    let tls = load_tls_config()?;
+    let auth_token = get_auth_token();
+
+    let nc_cell: Arc<RwLock<Option<nats::Connection>>> = Arc::new(RwLock::new(None));
+    let nc = connect_with_backoff(
+        &host,
+        &nc_cell,
+        {
+            let nc_cell = nc_cell.clone();
+            let subs = subs.clone();
+            let misc_subs = misc_subs.clone();
+            let blocklist = blocklist.clone();
+            let inbound_concurrency = inbound_concurrency.clone();
+            let tls = tls.clone();
+            let auth_token = auth_token.clone();
+            move || {
+                build_options(
+                    nc_cell.clone(),
+                    subs.clone(),
+                    misc_subs.clone(),
+                    blocklist.clone(),
+                    inbound_concurrency.clone(),
+                    tls.clone(),
+                    auth_token.clone(),
+                )
+            }
+        },
+    )?;
+    Ok(nc)
+}
+
+fn get_timeout() -> Duration {
+    Duration::from_millis(get_millis_env(
+        LATTICE_RPC_TIMEOUT_KEY,
+        DEFAULT_LATTICE_RPC_TIMEOUT_MILLIS,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_blocklist() -> BlockList {
+        Arc::new(RwLock::new(HashMap::new()))
+    }
+
+    #[test]
+    fn merge_entry_a_permanent_block_always_wins() {
+        let blocklist = empty_blocklist();
+        merge_entry(&blocklist, &BlockListEntry { origin: "evil".to_string(), expires_at: Some(100) });
+        merge_entry(&blocklist, &BlockListEntry { origin: "evil".to_string(), expires_at: None });
+        // A later, shorter-lived expiry must not un-permanent a permanent block.
+        merge_entry(&blocklist, &BlockListEntry { origin: "evil".to_string(), expires_at: Some(200) });
+
+        assert_eq!(blocklist.read().unwrap().get("evil"), Some(&None));
+    }
+
+    #[test]
+    fn merge_entry_keeps_the_longer_lived_expiry() {
+        let blocklist = empty_blocklist();
+        merge_entry(&blocklist, &BlockListEntry { origin: "evil".to_string(), expires_at: Some(100) });
+        merge_entry(&blocklist, &BlockListEntry { origin: "evil".to_string(), expires_at: Some(50) });
+        merge_entry(&blocklist, &BlockListEntry { origin: "evil".to_string(), expires_at: Some(300) });
+
+        assert_eq!(blocklist.read().unwrap().get("evil"), Some(&Some(300)));
+    }
+
+    #[test]
+    fn is_blocked_treats_a_missing_origin_as_not_blocked() {
+        let blocklist = empty_blocklist();
+        assert!(!is_blocked(&blocklist, "nobody"));
+    }
+
+    #[test]
+    fn is_blocked_treats_a_permanent_entry_as_always_blocked() {
+        let blocklist = empty_blocklist();
+        merge_entry(&blocklist, &BlockListEntry { origin: "evil".to_string(), expires_at: None });
+        assert!(is_blocked(&blocklist, "evil"));
+    }
+
+    #[test]
+    fn is_blocked_ages_out_an_expired_entry() {
+        let blocklist = empty_blocklist();
+        merge_entry(
+            &blocklist,
+            &BlockListEntry { origin: "evil".to_string(), expires_at: Some(now_unix() - 1) },
+        );
+        assert!(!is_blocked(&blocklist, "evil"));
+
+        merge_entry(
+            &blocklist,
+            &BlockListEntry { origin: "evil".to_string(), expires_at: Some(now_unix() + 60) },
+        );
+        assert!(is_blocked(&blocklist, "evil"));
+    }
+
+    #[test]
+    fn retries_exhausted_zero_max_retries_means_retry_forever() {
+        assert!(!retries_exhausted(1, 0));
+        assert!(!retries_exhausted(1_000_000, 0));
+    }
+
+    #[test]
+    fn retries_exhausted_stops_once_attempt_reaches_max_retries() {
+        assert!(!retries_exhausted(1, 3));
+        assert!(!retries_exhausted(2, 3));
+        assert!(retries_exhausted(3, 3));
+        assert!(retries_exhausted(4, 3));
+    }
+
+    #[test]
+    fn retries_exhausted_a_max_retries_of_one_fails_after_the_first_attempt() {
+        assert!(retries_exhausted(1, 1));
+    }
+
+    fn registration(entity_id: &str, expires_at: u64) -> Registration {
+        Registration {
+            host_id: "host1".to_string(),
+            entity_id: entity_id.to_string(),
+            metadata: HashMap::new(),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn active_registrations_filters_out_expired_entries() {
+        let discovery: DiscoveryMap = Arc::new(RwLock::new(HashMap::new()));
+        let mut entities = HashMap::new();
+        entities.insert("live".to_string(), registration("live", 100));
+        entities.insert("dead".to_string(), registration("dead", 50));
+        discovery.write().unwrap().insert("ns".to_string(), entities);
+
+        let found = active_registrations(&discovery, "ns", 60);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].entity_id, "live");
+    }
+
+    #[test]
+    fn active_registrations_returns_empty_for_an_unknown_namespace() {
+        let discovery: DiscoveryMap = Arc::new(RwLock::new(HashMap::new()));
+        assert!(active_registrations(&discovery, "nope", 0).is_empty());
+    }
+
+    #[test]
+    fn concurrency_gate_try_acquire_fails_once_exhausted() {
+        let gate = ConcurrencyGate::new(2);
+        assert_eq!(gate.available_permits(), 2);
+
+        let first = gate.try_acquire();
+        assert!(first.is_some());
+        assert_eq!(gate.available_permits(), 1);
+
+        let second = gate.try_acquire();
+        assert!(second.is_some());
+        assert_eq!(gate.available_permits(), 0);
+
+        assert!(gate.try_acquire().is_none());
+    }
+
+    #[test]
+    fn concurrency_gate_releases_its_permit_on_drop() {
+        let gate = ConcurrencyGate::new(1);
+        {
+            let _permit = gate.acquire_blocking();
+            assert_eq!(gate.available_permits(), 0);
         }
-        Err(_) => Duration::from_millis(DEFAULT_LATTICE_RPC_TIMEOUT_MILLIS),
+        assert_eq!(gate.available_permits(), 1);
     }
 }
@@ -1,28 +1,276 @@
 use super::host::{Invocation, InvocationResponse};
 use crossbeam::{Receiver, Sender};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 pub(crate) type InvokerPair = (Sender<Invocation>, Receiver<InvocationResponse>);
 
+// A pair is ejected after this many consecutive failures/timeouts...
+const FAILURE_THRESHOLD: u32 = 3;
+// ...and re-admitted, via a single probe request, once it's been ejected this long.
+const EJECT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// How `Router::get_pair` picks among several pairs registered for the same ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalancePolicy {
+    /// Cycle through healthy pairs in turn.
+    RoundRobin,
+    /// Prefer whichever healthy pair currently has the fewest in-flight invocations.
+    LeastPending,
+}
+
+impl Default for BalancePolicy {
+    fn default() -> Self {
+        BalancePolicy::RoundRobin
+    }
+}
+
+struct PairEntry {
+    pair: InvokerPair,
+    pending: usize,
+    consecutive_failures: u32,
+    ejected_at: Option<Instant>,
+    probing: bool,
+}
+
+impl PairEntry {
+    fn new(pair: InvokerPair) -> Self {
+        PairEntry {
+            pair,
+            pending: 0,
+            consecutive_failures: 0,
+            ejected_at: None,
+            probing: false,
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.ejected_at.is_none()
+    }
+}
+
+// `pairs` slots are stable identifiers: removing a pair tombstones its slot
+// (sets it to `None`) rather than shifting the `Vec`, so an index handed out
+// by `select` stays valid for the matching `record_result` even if another
+// pair is removed in between.
+#[derive(Default)]
+struct Route {
+    pairs: Vec<Option<PairEntry>>,
+    next: AtomicUsize,
+}
+
+impl Route {
+    // Picks a pair to serve the next invocation: any pair not currently ejected is
+    // a candidate, plus any ejected pair past its cooldown with no probe already
+    // in flight (a single health probe per ejected pair).
+    fn select(&mut self, policy: BalancePolicy) -> Option<(usize, InvokerPair)> {
+        let candidates: Vec<usize> = self
+            .pairs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| slot.as_ref().map(|p| (i, p)))
+            .filter(|(_, p)| {
+                p.is_healthy() || (!p.probing && p.ejected_at.unwrap().elapsed() >= EJECT_COOLDOWN)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let chosen = match policy {
+            BalancePolicy::RoundRobin => {
+                let i = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                candidates[i]
+            }
+            BalancePolicy::LeastPending => *candidates
+                .iter()
+                .min_by_key(|&&i| self.pairs[i].as_ref().unwrap().pending)
+                .unwrap(),
+        };
+
+        let entry = self.pairs[chosen].as_mut().unwrap();
+        if !entry.is_healthy() {
+            entry.probing = true;
+        }
+        entry.pending += 1;
+        Some((chosen, entry.pair.clone()))
+    }
+
+    // Updates health state from the outcome of an invocation previously handed
+    // out by `select`, ejecting or re-admitting the pair as needed. A no-op if
+    // the slot was removed (tombstoned) since the invocation was dispatched.
+    fn record_result(&mut self, index: usize, success: bool) {
+        if let Some(Some(entry)) = self.pairs.get_mut(index) {
+            entry.pending = entry.pending.saturating_sub(1);
+            entry.probing = false;
+            if success {
+                entry.consecutive_failures = 0;
+                entry.ejected_at = None;
+            } else {
+                entry.consecutive_failures += 1;
+                if entry.consecutive_failures >= FAILURE_THRESHOLD {
+                    entry.ejected_at = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pairs.iter().all(|slot| slot.is_none())
+    }
+}
+
 #[derive(Default)]
 pub struct Router {
-    routes: HashMap<String, InvokerPair>,
+    routes: Mutex<HashMap<String, Route>>,
+    policy: BalancePolicy,
 }
 
 impl Router {
+    pub fn new(policy: BalancePolicy) -> Self {
+        Router {
+            routes: Mutex::new(HashMap::new()),
+            policy,
+        }
+    }
+
+    /// Registers a new pair for `id` and returns the stable slot index it was
+    /// assigned, for later use with `remove_route`.
     pub fn add_route(
-        &mut self,
+        &self,
         id: String,
         inv_s: Sender<Invocation>,
         resp_r: Receiver<InvocationResponse>,
-    ) {
-        self.routes.insert(id, (inv_s, resp_r));
+    ) -> usize {
+        let mut routes = self.routes.lock().unwrap();
+        let route = routes.entry(id).or_insert_with(Route::default);
+        route.pairs.push(Some(PairEntry::new((inv_s, resp_r))));
+        route.pairs.len() - 1
+    }
+
+    /// Removes the pair at `index`, tombstoning its slot so any other index
+    /// already handed out by `get_pair`/`healthy_pairs` for this route stays
+    /// valid. Drops the route entirely once every slot is empty.
+    pub fn remove_route(&self, id: &str, index: usize) {
+        let mut routes = self.routes.lock().unwrap();
+        if let Some(route) = routes.get_mut(id) {
+            if let Some(slot) = route.pairs.get_mut(index) {
+                *slot = None;
+            }
+            if route.is_empty() {
+                routes.remove(id);
+            }
+        }
+    }
+
+    /// Selects a pair to invoke for `id` per the router's load-balance policy,
+    /// skipping any pair ejected by the circuit breaker, except that an ejected
+    /// pair past its cooldown is handed out once as a health probe. The returned
+    /// index should be passed to `record_result` once the invocation completes.
+    pub fn get_pair(&self, id: &str) -> Option<(usize, InvokerPair)> {
+        self.routes.lock().unwrap().get_mut(id)?.select(self.policy)
+    }
+
+    /// Reports whether the invocation dispatched to `(id, index)` succeeded, so
+    /// the circuit breaker can eject a failing pair or re-admit a recovered one.
+    pub fn record_result(&self, id: &str, index: usize, success: bool) {
+        if let Some(route) = self.routes.lock().unwrap().get_mut(id) {
+            route.record_result(index, success);
+        }
+    }
+
+    /// Enumerates the currently-healthy pairs registered for `id`, for metrics.
+    pub fn healthy_pairs(&self, id: &str) -> Vec<(usize, InvokerPair)> {
+        match self.routes.lock().unwrap().get(id) {
+            Some(route) => route
+                .pairs
+                .iter()
+                .enumerate()
+                .filter_map(|(i, slot)| slot.as_ref().map(|p| (i, p)))
+                .filter(|(_, p)| p.is_healthy())
+                .map(|(i, p)| (i, p.pair.clone()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> InvokerPair {
+        let (inv_s, _inv_r) = crossbeam::bounded(1);
+        let (_resp_s, resp_r) = crossbeam::bounded(1);
+        (inv_s, resp_r)
     }
 
-    pub fn get_pair(&self, id: &str) -> Option<InvokerPair> {
-        match self.routes.get(id) {
-            Some(p) => Some(p.clone()),
-            None => None,
+    #[test]
+    fn round_robin_cycles_through_healthy_pairs() {
+        let router = Router::new(BalancePolicy::RoundRobin);
+        for _ in 0..3 {
+            let (inv_s, resp_r) = pair();
+            router.add_route("actor".to_string(), inv_s, resp_r);
         }
+
+        let mut seen = Vec::new();
+        for _ in 0..6 {
+            let (index, _) = router.get_pair("actor").unwrap();
+            router.record_result("actor", index, true);
+            seen.push(index);
+        }
+
+        assert_eq!(seen, vec![0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn least_pending_prefers_the_pair_with_fewer_in_flight_invocations() {
+        let router = Router::new(BalancePolicy::LeastPending);
+        let (inv_s, resp_r) = pair();
+        router.add_route("actor".to_string(), inv_s, resp_r);
+        let (inv_s, resp_r) = pair();
+        router.add_route("actor".to_string(), inv_s, resp_r);
+
+        // Hand out a pair without resolving it, so it carries one pending
+        // invocation; the next `select` should skip past it.
+        let (busy_index, _) = router.get_pair("actor").unwrap();
+        let (next_index, _) = router.get_pair("actor").unwrap();
+
+        assert_ne!(busy_index, next_index);
+    }
+
+    #[test]
+    fn ejects_after_threshold_failures_and_reprobes_once_cooldown_elapses() {
+        let router = Router::new(BalancePolicy::RoundRobin);
+        let (inv_s, resp_r) = pair();
+        router.add_route("actor".to_string(), inv_s, resp_r);
+
+        for _ in 0..FAILURE_THRESHOLD {
+            let (index, _) = router.get_pair("actor").unwrap();
+            router.record_result("actor", index, false);
+        }
+
+        // Ejected and still within its cooldown: no candidates.
+        assert!(router.get_pair("actor").is_none());
+        assert!(router.healthy_pairs("actor").is_empty());
+
+        // Backdate the ejection past the cooldown window to simulate time
+        // having elapsed, without sleeping in the test.
+        {
+            let mut routes = router.routes.lock().unwrap();
+            let entry = routes.get_mut("actor").unwrap().pairs[0].as_mut().unwrap();
+            entry.ejected_at = Instant::now().checked_sub(EJECT_COOLDOWN + Duration::from_secs(1));
+        }
+
+        // Past cooldown: exactly one probe is allowed through.
+        let (index, _) = router.get_pair("actor").unwrap();
+        router.record_result("actor", index, true);
+
+        // A successful probe fully re-admits the pair.
+        assert_eq!(router.healthy_pairs("actor").len(), 1);
     }
 }